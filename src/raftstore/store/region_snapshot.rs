@@ -11,15 +11,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use byteorder::{BigEndian, ReadBytesExt};
+use futures::Future;
+use futures_cpupool::{CpuFuture, CpuPool};
 use kvproto::metapb::Region;
 use rocksdb::{DBIterator, DBVector, TablePropertiesCollection, DB};
 use std::cmp;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Bound::{Excluded, Included, Unbounded};
 use std::sync::Arc;
 
+use raftstore::coprocessor::properties::RangeProperties;
 use raftstore::store::engine::{IterOption, Peekable, Snapshot, SyncSnapshot};
 use raftstore::store::{keys, util, PeerStorage};
 use raftstore::{Error, Result};
 use storage::engine::Iterator;
+use storage::CF_DEFAULT;
+use util::codec::number;
 use util::metrics::CRITICAL_ERROR;
 use util::{panic_when_unexpected_key_or_data, set_panic_mark};
 
@@ -104,10 +113,207 @@ impl RegionSnapshot {
         Ok(())
     }
 
+    // scan_reverse scans database in range [start_key, end_key) in descending key order, calls
+    // function f for each iteration, if f returns false, terminates this scan. It mirrors `scan`
+    // but walks the region backwards, stopping at `start_key` (inclusive of the region lower
+    // bound) instead of requiring a `storage::Cursor`.
+    pub fn scan_reverse<F>(
+        &self,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        let iter_opt =
+            IterOption::new(Some(start_key.to_vec()), Some(end_key.to_vec()), fill_cache);
+        self.scan_reverse_impl(self.iter(iter_opt), f)
+    }
+
+    // like `scan_reverse`, only on a specific column family.
+    pub fn scan_cf_reverse<F>(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        let iter_opt =
+            IterOption::new(Some(start_key.to_vec()), Some(end_key.to_vec()), fill_cache);
+        self.scan_reverse_impl(self.iter_cf(cf, iter_opt)?, f)
+    }
+
+    fn scan_reverse_impl<F>(&self, mut it: RegionIterator, mut f: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        // Start from the last key inside the iterator's bounds and walk back. `seek_to_last` honors
+        // the exclusive upper bound derived from `end_key`, so `end_key` itself is excluded and an
+        // open/over-region bound is clamped to the region end, exactly mirroring `scan`. Seeking
+        // `end_key` directly would both reject over-region bounds and risk including `end_key`.
+        let mut it_valid = it.seek_to_last()?;
+        while it_valid {
+            it_valid = f(it.key(), it.value())? && it.prev()?;
+        }
+        Ok(())
+    }
+
+    // scan_prefix scans all keys sharing `prefix` in ascending order, deriving the scan bounds
+    // automatically: the lower bound is `prefix` and the upper bound is the lexicographic
+    // successor of `prefix`. Both bounds are still intersected with the region's key space by the
+    // `RegionIterator`, so callers never scan past the region or re-derive prefix successor logic.
+    pub fn scan_prefix<F>(&self, prefix: &[u8], fill_cache: bool, f: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        let iter_opt = IterOption::new(Some(prefix.to_vec()), prefix_end_key(prefix), fill_cache);
+        self.scan_prefix_impl(self.iter(iter_opt), f)
+    }
+
+    // like `scan_prefix`, only on a specific column family.
+    pub fn scan_prefix_cf<F>(&self, cf: &str, prefix: &[u8], fill_cache: bool, f: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        let iter_opt = IterOption::new(Some(prefix.to_vec()), prefix_end_key(prefix), fill_cache);
+        self.scan_prefix_impl(self.iter_cf(cf, iter_opt)?, f)
+    }
+
+    fn scan_prefix_impl<F>(&self, mut it: RegionIterator, mut f: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        // Start from the region-clamped lower bound rather than seeking `prefix`, which may lie
+        // outside the region.
+        let mut it_valid = it.seek_to_first()?;
+        while it_valid {
+            it_valid = f(it.key(), it.value())? && it.next()?;
+        }
+        Ok(())
+    }
+
+    // scan_typed behaves like `scan` but decodes each raw value according to `codec` before
+    // handing it to `f`, so tools scanning a fixed-schema key space need not open-code decode logic
+    // at every call site. A decode error surfaces through the `Result` channel, terminating the
+    // scan instead of silently yielding corrupt output.
+    pub fn scan_typed<F>(
+        &self,
+        codec: ValueCodec,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        mut f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[u8], TypedValue) -> Result<bool>,
+    {
+        self.scan(start_key, end_key, fill_cache, |k, v| f(k, codec.decode(v)?))
+    }
+
+    // like `scan_typed`, only on a specific column family.
+    pub fn scan_typed_cf<F>(
+        &self,
+        cf: &str,
+        codec: ValueCodec,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        mut f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[u8], TypedValue) -> Result<bool>,
+    {
+        self.scan_cf(cf, start_key, end_key, fill_cache, |k, v| {
+            f(k, codec.decode(v)?)
+        })
+    }
+
     pub fn get_properties_cf(&self, cf: &str) -> Result<TablePropertiesCollection> {
         util::get_region_properties_cf(&self.snap.get_db(), cf, self.get_region())
     }
 
+    // scan_parallel partitions the region into roughly balanced sub-ranges at the boundaries of the
+    // SSTs overlapping it (discovered through `get_properties_cf`), bounding the partition count to
+    // `concurrency`, and scans each sub-range on the caller-provided `pool` (reused across calls
+    // rather than rebuilt per invocation). `init` produces a fresh reduction state per partition and
+    // `reducer` folds every key/value pair into it; the per-partition states are
+    // returned in partition order for the caller to combine. This lets large full-region scans
+    // (checksum, bulk export, ...) use multiple cores instead of a single serial iterator.
+    pub fn scan_parallel<S, I, R>(
+        &self,
+        pool: &CpuPool,
+        cf: &str,
+        concurrency: usize,
+        init: I,
+        reducer: R,
+    ) -> Result<Vec<S>>
+    where
+        S: Send + 'static,
+        I: Fn() -> S + Send + Sync + 'static,
+        R: Fn(&mut S, &[u8], &[u8]) + Send + Sync + 'static,
+    {
+        let region = self.get_region();
+        let start = region.get_start_key().to_vec();
+        let end = region.get_end_key().to_vec();
+        let split_points = self.sst_split_points(cf)?;
+        let ranges = partition_ranges(&start, &end, split_points, cmp::max(concurrency, 1));
+
+        let init = Arc::new(init);
+        let reducer = Arc::new(reducer);
+        let mut futures = Vec::with_capacity(ranges.len());
+        for (lower, upper) in ranges {
+            // Skip partitions whose lower bound is not a key this region may seek.
+            if util::check_key_in_region_inclusive(&lower, region).is_err() {
+                continue;
+            }
+            let snap = self.clone();
+            let cf = cf.to_owned();
+            let init = Arc::clone(&init);
+            let reducer = Arc::clone(&reducer);
+            let future: CpuFuture<S, Error> = pool.spawn_fn(move || -> Result<S> {
+                let mut state = init();
+                snap.scan_cf(&cf, &lower, &upper, false, |k, v| {
+                    reducer(&mut state, k, v);
+                    Ok(true)
+                })?;
+                Ok(state)
+            });
+            futures.push(future);
+        }
+
+        let mut states = Vec::with_capacity(futures.len());
+        for future in futures {
+            states.push(future.wait()?);
+        }
+        Ok(states)
+    }
+
+    // sst_split_points collects the smallest/largest keys of every SST overlapping the region,
+    // translated back into region-local (origin) key space, to be used as partition boundaries.
+    // SSTs whose `user_collected_properties()` do not carry decodable `RangeProperties` (e.g.
+    // legacy or mixed files written without the range-properties collector) are skipped, exactly as
+    // the approximate-size paths do; only files that do expose the property contribute boundaries.
+    // When none do, the empty result degrades to a single serial partition via `partition_ranges`.
+    fn sst_split_points(&self, cf: &str) -> Result<Vec<Vec<u8>>> {
+        let collection = self.get_properties_cf(cf)?;
+        let mut points = vec![];
+        for (_, v) in &*collection {
+            let props = match RangeProperties::decode(&v.user_collected_properties()) {
+                Ok(props) => props,
+                Err(_) => continue,
+            };
+            points.push(keys::origin_key(props.smallest_key()).to_vec());
+            points.push(keys::origin_key(props.largest_key()).to_vec());
+        }
+        Ok(points)
+    }
+
     pub fn get_start_key(&self) -> &[u8] {
         self.region.get_start_key()
     }
@@ -140,6 +346,445 @@ impl Peekable for RegionSnapshot {
     }
 }
 
+/// Describes how the raw bytes of a value should be interpreted by `scan_typed`.
+#[derive(Clone, Copy, Debug)]
+pub enum ValueCodec {
+    /// Pass the raw bytes through undecoded.
+    Bytes,
+    /// A big-endian unsigned 64-bit integer, matching `Peekable::get_u64`.
+    U64,
+    /// A big-endian signed 64-bit integer, matching `Peekable::get_i64`.
+    I64,
+    /// A big-endian IEEE-754 double.
+    F64,
+    /// A single byte, zero meaning `false` and any other value `true`.
+    Bool,
+}
+
+/// A value produced by `scan_typed`, already decoded from its `ValueCodec`. The decoded value is
+/// owned so callbacks can collect it into a container outside the transient iterator borrow.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypedValue {
+    Bytes(Vec<u8>),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+}
+
+impl ValueCodec {
+    fn decode(self, value: &[u8]) -> Result<TypedValue> {
+        let mut buf = value;
+        match self {
+            ValueCodec::Bytes => Ok(TypedValue::Bytes(value.to_vec())),
+            // Reuse the same number-decode path `Peekable::get_u64`/`get_i64` are built on so a
+            // single big-endian integer format exists across the crate.
+            ValueCodec::U64 => number::decode_u64(&mut buf)
+                .map(TypedValue::U64)
+                .map_err(|e| box_err!(e)),
+            ValueCodec::I64 => number::decode_u64(&mut buf)
+                .map(|n| TypedValue::I64(n as i64))
+                .map_err(|e| box_err!(e)),
+            ValueCodec::F64 => buf
+                .read_f64::<BigEndian>()
+                .map(TypedValue::F64)
+                .map_err(|e| box_err!(e)),
+            ValueCodec::Bool => buf
+                .read_u8()
+                .map(|b| TypedValue::Bool(b != 0))
+                .map_err(|e| box_err!(e)),
+        }
+    }
+}
+
+/// The synchronous read surface of a region, split out from the concrete `RegionSnapshot` so that
+/// coprocessor and other request paths can depend on the trait instead of the struct. This mirrors
+/// the way client modules separate a synchronous client (`SyncClient`) from its asynchronous
+/// counterpart; see `AsyncRegionReader` for the future-returning side.
+///
+/// Note: the generic `scan`/`scan_cf` callbacks make this trait **not** object-safe, so callers
+/// depend on it through generic bounds (`fn f<R: RegionReader>(r: &R)`), not as `dyn RegionReader`.
+/// A trait object would require boxing the callback surface to `FnMut`, which the current callers
+/// do not need.
+pub trait RegionReader {
+    fn get_value(&self, key: &[u8]) -> Result<Option<DBVector>>;
+    fn get_value_cf(&self, cf: &str, key: &[u8]) -> Result<Option<DBVector>>;
+
+    fn scan<F>(&self, start_key: &[u8], end_key: &[u8], fill_cache: bool, f: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>;
+
+    fn scan_cf<F>(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>;
+
+    fn iter(&self, iter_opt: IterOption) -> RegionIterator;
+    fn iter_cf(&self, cf: &str, iter_opt: IterOption) -> Result<RegionIterator>;
+    fn get_properties_cf(&self, cf: &str) -> Result<TablePropertiesCollection>;
+}
+
+impl RegionReader for RegionSnapshot {
+    fn get_value(&self, key: &[u8]) -> Result<Option<DBVector>> {
+        Peekable::get_value(self, key)
+    }
+
+    fn get_value_cf(&self, cf: &str, key: &[u8]) -> Result<Option<DBVector>> {
+        Peekable::get_value_cf(self, cf, key)
+    }
+
+    fn scan<F>(&self, start_key: &[u8], end_key: &[u8], fill_cache: bool, f: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        RegionSnapshot::scan(self, start_key, end_key, fill_cache, f)
+    }
+
+    fn scan_cf<F>(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        RegionSnapshot::scan_cf(self, cf, start_key, end_key, fill_cache, f)
+    }
+
+    fn iter(&self, iter_opt: IterOption) -> RegionIterator {
+        RegionSnapshot::iter(self, iter_opt)
+    }
+
+    fn iter_cf(&self, cf: &str, iter_opt: IterOption) -> Result<RegionIterator> {
+        RegionSnapshot::iter_cf(self, cf, iter_opt)
+    }
+
+    fn get_properties_cf(&self, cf: &str) -> Result<TablePropertiesCollection> {
+        RegionSnapshot::get_properties_cf(self, cf)
+    }
+}
+
+/// The asynchronous counterpart of `RegionReader`. Each method off-loads the synchronous read body
+/// onto the supplied blocking `CpuPool` and hands back a future, so async request paths never block
+/// the event loop on a RocksDB iterator. Values are materialized into owned buffers because the
+/// borrowed `DBVector`/iterator handles cannot cross the pool boundary.
+pub trait AsyncRegionReader {
+    fn async_get_value(&self, pool: &CpuPool, key: Vec<u8>) -> CpuFuture<Option<Vec<u8>>, Error>;
+
+    fn async_get_value_cf(
+        &self,
+        pool: &CpuPool,
+        cf: String,
+        key: Vec<u8>,
+    ) -> CpuFuture<Option<Vec<u8>>, Error>;
+
+    fn async_scan(
+        &self,
+        pool: &CpuPool,
+        start_key: Vec<u8>,
+        end_key: Vec<u8>,
+        fill_cache: bool,
+    ) -> CpuFuture<Vec<(Vec<u8>, Vec<u8>)>, Error>;
+
+    fn async_scan_cf(
+        &self,
+        pool: &CpuPool,
+        cf: String,
+        start_key: Vec<u8>,
+        end_key: Vec<u8>,
+        fill_cache: bool,
+    ) -> CpuFuture<Vec<(Vec<u8>, Vec<u8>)>, Error>;
+}
+
+impl AsyncRegionReader for RegionSnapshot {
+    fn async_get_value(&self, pool: &CpuPool, key: Vec<u8>) -> CpuFuture<Option<Vec<u8>>, Error> {
+        let snap = self.clone();
+        pool.spawn_fn(move || {
+            Peekable::get_value(&snap, &key).map(|v| v.map(|v| v.to_vec()))
+        })
+    }
+
+    fn async_get_value_cf(
+        &self,
+        pool: &CpuPool,
+        cf: String,
+        key: Vec<u8>,
+    ) -> CpuFuture<Option<Vec<u8>>, Error> {
+        let snap = self.clone();
+        pool.spawn_fn(move || {
+            Peekable::get_value_cf(&snap, &cf, &key).map(|v| v.map(|v| v.to_vec()))
+        })
+    }
+
+    fn async_scan(
+        &self,
+        pool: &CpuPool,
+        start_key: Vec<u8>,
+        end_key: Vec<u8>,
+        fill_cache: bool,
+    ) -> CpuFuture<Vec<(Vec<u8>, Vec<u8>)>, Error> {
+        let snap = self.clone();
+        pool.spawn_fn(move || {
+            let mut pairs = vec![];
+            snap.scan(&start_key, &end_key, fill_cache, |k, v| {
+                pairs.push((k.to_vec(), v.to_vec()));
+                Ok(true)
+            })?;
+            Ok(pairs)
+        })
+    }
+
+    fn async_scan_cf(
+        &self,
+        pool: &CpuPool,
+        cf: String,
+        start_key: Vec<u8>,
+        end_key: Vec<u8>,
+        fill_cache: bool,
+    ) -> CpuFuture<Vec<(Vec<u8>, Vec<u8>)>, Error> {
+        let snap = self.clone();
+        pool.spawn_fn(move || {
+            let mut pairs = vec![];
+            snap.scan_cf(&cf, &start_key, &end_key, fill_cache, |k, v| {
+                pairs.push((k.to_vec(), v.to_vec()));
+                Ok(true)
+            })?;
+            Ok(pairs)
+        })
+    }
+}
+
+/// Per-column-family staged mutations. Each key keeps a stack of `(epoch, value)` versions, newest
+/// last, where a `None` value encodes a tombstone. Keeping the full version stack lets a rollback
+/// restore whatever a staged batch overwrote, not merely drop the latest write.
+#[derive(Default, Debug)]
+struct CfOverlay {
+    entries: BTreeMap<Vec<u8>, Vec<(u64, Option<Vec<u8>>)>>,
+}
+
+impl CfOverlay {
+    fn stage(&mut self, epoch: u64, key: Vec<u8>, value: Option<Vec<u8>>) {
+        self.entries.entry(key).or_default().push((epoch, value));
+    }
+
+    // latest returns the current staged value for `key`: `Some(Some(v))` for a write,
+    // `Some(None)` for a tombstone, `None` when nothing is staged.
+    fn latest(&self, key: &[u8]) -> Option<&Option<Vec<u8>>> {
+        self.entries
+            .get(key)
+            .and_then(|versions| versions.last())
+            .map(|&(_, ref v)| v)
+    }
+
+    // rollback_to drops every version staged at or after `epoch`, pruning keys left empty.
+    fn rollback_to(&mut self, epoch: u64) {
+        self.entries.retain(|_, versions| {
+            versions.retain(|&(e, _)| e < epoch);
+            !versions.is_empty()
+        });
+    }
+}
+
+/// A pending-write overlay layered on top of a `RegionSnapshot`. Staged mutations live in memory,
+/// keyed per column family and versioned by a monotonic epoch counter, so a batch of staged writes
+/// can be rolled back by truncating to a prior epoch. Reads merge the overlay over the base
+/// snapshot — a staged entry wins over the base (a tombstone yielding "not found"), otherwise the
+/// read falls through. This lets request handlers preview the effect of not-yet-committed writes
+/// against a consistent region view.
+pub struct OverlaySnapshot {
+    base: RegionSnapshot,
+    overlays: HashMap<String, CfOverlay>,
+    epoch: u64,
+}
+
+impl OverlaySnapshot {
+    pub fn new(base: RegionSnapshot) -> OverlaySnapshot {
+        OverlaySnapshot {
+            base,
+            overlays: HashMap::new(),
+            epoch: 0,
+        }
+    }
+
+    pub fn get_region(&self) -> &Region {
+        self.base.get_region()
+    }
+
+    // seal_epoch ends the current staging batch and returns the epoch of the next batch, i.e. the
+    // first epoch not covered by anything staged so far. Passing it to `rollback_to` later discards
+    // everything staged after this point while keeping the just-sealed batch intact.
+    pub fn seal_epoch(&mut self) -> u64 {
+        self.epoch += 1;
+        self.epoch
+    }
+
+    // rollback_to discards every mutation staged at or after `epoch` across all column families.
+    pub fn rollback_to(&mut self, epoch: u64) {
+        for overlay in self.overlays.values_mut() {
+            overlay.rollback_to(epoch);
+        }
+        self.epoch = epoch;
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.put_cf(CF_DEFAULT, key, value);
+    }
+
+    pub fn put_cf(&mut self, cf: &str, key: &[u8], value: &[u8]) {
+        let epoch = self.epoch;
+        self.overlays
+            .entry(cf.to_owned())
+            .or_default()
+            .stage(epoch, key.to_vec(), Some(value.to_vec()));
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.delete_cf(CF_DEFAULT, key);
+    }
+
+    pub fn delete_cf(&mut self, cf: &str, key: &[u8]) {
+        let epoch = self.epoch;
+        self.overlays
+            .entry(cf.to_owned())
+            .or_default()
+            .stage(epoch, key.to_vec(), None);
+    }
+
+    pub fn get_value(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.get_value_cf(CF_DEFAULT, key)
+    }
+
+    pub fn get_value_cf(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        if let Some(staged) = self.overlays.get(cf).and_then(|o| o.latest(key)) {
+            util::check_key_in_region(key, self.get_region())?;
+            return Ok(staged.clone());
+        }
+        let base = if cf == CF_DEFAULT {
+            Peekable::get_value(&self.base, key)?
+        } else {
+            Peekable::get_value_cf(&self.base, cf, key)?
+        };
+        Ok(base.map(|v| v.to_vec()))
+    }
+
+    // scan merges the overlay over the base snapshot in ascending key order, invoking `f` for each
+    // surviving key. Tombstones mask the corresponding base key; overlay keys outside the region
+    // are skipped so the base iterator's region clamping still bounds the whole walk.
+    pub fn scan<F>(&self, start_key: &[u8], end_key: &[u8], fill_cache: bool, f: F) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        self.scan_cf(CF_DEFAULT, start_key, end_key, fill_cache, f)
+    }
+
+    pub fn scan_cf<F>(
+        &self,
+        cf: &str,
+        start_key: &[u8],
+        end_key: &[u8],
+        fill_cache: bool,
+        mut f: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<bool>,
+    {
+        let iter_opt =
+            IterOption::new(Some(start_key.to_vec()), Some(end_key.to_vec()), fill_cache);
+        let mut it = if cf == CF_DEFAULT {
+            self.base.iter(iter_opt)
+        } else {
+            self.base.iter_cf(cf, iter_opt)?
+        };
+        let mut base_valid = it.seek(start_key)?;
+
+        let empty = CfOverlay::default();
+        let overlay = self.overlays.get(cf).unwrap_or(&empty);
+        // An empty `end_key` means "open to the region end", matching `scan`/`set_upper_bound`.
+        // Building an `Excluded(b"")` upper bound would panic, so leave the range open and let the
+        // region clamp below bound the overlay walk.
+        let upper = if end_key.is_empty() {
+            Unbounded
+        } else {
+            Excluded(end_key)
+        };
+        let mut ov_iter = overlay
+            .entries
+            .range::<[u8], _>((Included(start_key), upper))
+            .peekable();
+        let region = self.get_region();
+
+        loop {
+            // Advance past overlay keys that fall outside the region bounds.
+            while let Some(&(k, _)) = ov_iter.peek() {
+                if util::check_key_in_region(k, region).is_ok() {
+                    break;
+                }
+                ov_iter.next();
+            }
+
+            let cont = match (base_valid, ov_iter.peek()) {
+                (false, None) => break,
+                (true, None) => {
+                    let cont = f(it.key(), it.value())?;
+                    base_valid = it.next()?;
+                    cont
+                }
+                (false, Some(&(k, versions))) => {
+                    let cont = emit_overlay(&mut f, k, versions)?;
+                    ov_iter.next();
+                    cont
+                }
+                (true, Some(&(k, versions))) => {
+                    match it.key().cmp(k.as_slice()) {
+                        cmp::Ordering::Less => {
+                            let cont = f(it.key(), it.value())?;
+                            base_valid = it.next()?;
+                            cont
+                        }
+                        cmp::Ordering::Greater => {
+                            let cont = emit_overlay(&mut f, k, versions)?;
+                            ov_iter.next();
+                            cont
+                        }
+                        cmp::Ordering::Equal => {
+                            // Overlay wins over the base for the same key.
+                            base_valid = it.next()?;
+                            let cont = emit_overlay(&mut f, k, versions)?;
+                            ov_iter.next();
+                            cont
+                        }
+                    }
+                }
+            };
+            if !cont {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+// emit_overlay invokes `f` for an overlay entry unless it is a tombstone, returning whether the
+// scan should continue (tombstones never terminate the scan on their own).
+fn emit_overlay<F>(f: &mut F, key: &[u8], versions: &[(u64, Option<Vec<u8>>)]) -> Result<bool>
+where
+    F: FnMut(&[u8], &[u8]) -> Result<bool>,
+{
+    match versions.last() {
+        Some(&(_, Some(ref value))) => f(key, value),
+        _ => Ok(true),
+    }
+}
+
 /// `RegionIterator` wrap a rocksdb iterator and only allow it to
 /// iterate in the region. It behaves as if underlying
 /// db only contains one region.
@@ -148,6 +793,64 @@ pub struct RegionIterator {
     region: Arc<Region>,
 }
 
+// prefix_end_key returns the smallest key that is lexicographically greater than every key
+// prefixed by `prefix`, i.e. the exclusive upper bound of the prefix range. It increments the
+// last non-0xFF byte and drops the trailing 0xFF bytes; if `prefix` is empty or all 0xFF there is
+// no such finite key, so `None` is returned and the range stays open (clamped to `enc_end_key`).
+fn prefix_end_key(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(&last) = end.last() {
+        if last != 0xFF {
+            *end.last_mut().unwrap() += 1;
+            return Some(end);
+        }
+        end.pop();
+    }
+    None
+}
+
+// partition_ranges slices `[start, end)` into at most `max_parts` consecutive sub-ranges at the
+// supplied split points. An empty `end` denotes the region's open upper bound and is preserved as
+// an open range. Split points are sorted, de-duplicated and clamped to the interior of the region;
+// when there are more than `max_parts - 1` of them a roughly even subset is kept so the partition
+// count stays bounded. With no usable split points the whole region is returned as a single range.
+fn partition_ranges(
+    start: &[u8],
+    end: &[u8],
+    mut split_points: Vec<Vec<u8>>,
+    max_parts: usize,
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let open_end = end.is_empty();
+    split_points.retain(|p| p.as_slice() > start && (open_end || p.as_slice() < end));
+    split_points.sort();
+    split_points.dedup();
+
+    if max_parts <= 1 || split_points.is_empty() {
+        return vec![(start.to_vec(), end.to_vec())];
+    }
+
+    // Keep at most `max_parts - 1` evenly spaced boundaries.
+    if split_points.len() > max_parts - 1 {
+        let step = split_points.len() / (max_parts - 1);
+        split_points = split_points
+            .into_iter()
+            .enumerate()
+            .filter(|&(i, _)| i % step == 0)
+            .map(|(_, p)| p)
+            .take(max_parts - 1)
+            .collect();
+    }
+
+    let mut ranges = Vec::with_capacity(split_points.len() + 1);
+    let mut lower = start.to_vec();
+    for point in split_points {
+        ranges.push((lower, point.clone()));
+        lower = point;
+    }
+    ranges.push((lower, end.to_vec()));
+    ranges
+}
+
 fn set_lower_bound(iter_opt: &mut IterOption, region: &Region) {
     let region_start_key = keys::enc_start_key(region);
     let lower_bound = match iter_opt.lower_bound() {
@@ -587,6 +1290,231 @@ mod tests {
         assert_eq!(res, expect);
     }
 
+    #[test]
+    fn test_reverse_scan() {
+        let path = TempDir::new("test-raftstore").unwrap();
+        let engines = new_temp_engine(&path);
+        let (store, base_data) = load_default_dataset(engines);
+
+        let snap = RegionSnapshot::new(&store);
+        let mut data = vec![];
+        snap.scan_reverse(b"a2", b"a7", false, |key, value| {
+            data.push((key.to_vec(), value.to_vec()));
+            Ok(true)
+        }).unwrap();
+
+        let mut expect = base_data[1..3].to_vec();
+        expect.reverse();
+        assert_eq!(data, expect);
+
+        data.clear();
+        snap.scan_reverse(b"a2", b"a7", false, |key, value| {
+            data.push((key.to_vec(), value.to_vec()));
+            Ok(false)
+        }).unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0], base_data[2]);
+
+        // An over-region end_key is clamped to the region upper bound, mirroring `scan`.
+        data.clear();
+        snap.scan_reverse(b"a2", &[0xFF, 0xFF], false, |key, value| {
+            data.push((key.to_vec(), value.to_vec()));
+            Ok(true)
+        }).unwrap();
+        assert_eq!(data, expect);
+
+        // end_key is exclusive: an in-range key that exists is not yielded.
+        data.clear();
+        snap.scan_reverse(b"a2", b"a5", false, |key, value| {
+            data.push((key.to_vec(), value.to_vec()));
+            Ok(true)
+        }).unwrap();
+        assert_eq!(data, vec![(b"a3".to_vec(), b"v3".to_vec())]);
+    }
+
+    #[test]
+    fn test_scan_prefix() {
+        assert_eq!(prefix_end_key(b"a"), Some(b"b".to_vec()));
+        assert_eq!(prefix_end_key(&[0x01, 0xFF]), Some(vec![0x02]));
+        assert_eq!(prefix_end_key(&[0xFF, 0xFF]), None);
+        assert_eq!(prefix_end_key(b""), None);
+
+        let path = TempDir::new("test-raftstore").unwrap();
+        let engines = new_temp_engine(&path);
+        let (store, base_data) = load_default_dataset(engines);
+
+        let snap = RegionSnapshot::new(&store);
+        let mut data = vec![];
+        snap.scan_prefix(b"a", false, |key, value| {
+            data.push((key.to_vec(), value.to_vec()));
+            Ok(true)
+        }).unwrap();
+        assert_eq!(data, base_data[1..3].to_vec());
+
+        data.clear();
+        snap.scan_prefix(b"a4", false, |key, value| {
+            data.push((key.to_vec(), value.to_vec()));
+            Ok(true)
+        }).unwrap();
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn test_async_region_reader() {
+        use futures::Future;
+        use futures_cpupool::Builder;
+
+        let path = TempDir::new("test-raftstore").unwrap();
+        let engines = new_temp_engine(&path);
+        let (store, base_data) = load_default_dataset(engines);
+
+        let snap = RegionSnapshot::new(&store);
+        let pool = Builder::new().pool_size(1).create();
+
+        let v = snap.async_get_value(&pool, b"a3".to_vec()).wait().unwrap();
+        assert_eq!(v, Some(b"v3".to_vec()));
+
+        let pairs = snap
+            .async_scan(&pool, b"a2".to_vec(), vec![0xFF, 0xFF], false)
+            .wait()
+            .unwrap();
+        assert_eq!(pairs, base_data[1..3].to_vec());
+    }
+
+    #[test]
+    fn test_overlay_snapshot() {
+        let path = TempDir::new("test-raftstore").unwrap();
+        let engines = new_temp_engine(&path);
+        let (store, _) = load_default_dataset(engines);
+
+        let base = RegionSnapshot::new(&store);
+        let mut overlay = OverlaySnapshot::new(base);
+
+        // Stage a write, a tombstone and an override on top of the base (a3, a5).
+        overlay.put(b"a4", b"v4");
+        overlay.delete(b"a3");
+        overlay.put(b"a5", b"v5new");
+
+        assert_eq!(overlay.get_value(b"a4").unwrap(), Some(b"v4".to_vec()));
+        assert_eq!(overlay.get_value(b"a3").unwrap(), None);
+        assert_eq!(overlay.get_value(b"a5").unwrap(), Some(b"v5new".to_vec()));
+
+        let mut data = vec![];
+        overlay.scan(b"a2", &[0xFF, 0xFF], false, |key, value| {
+            data.push((key.to_vec(), value.to_vec()));
+            Ok(true)
+        }).unwrap();
+        assert_eq!(
+            data,
+            vec![
+                (b"a4".to_vec(), b"v4".to_vec()),
+                (b"a5".to_vec(), b"v5new".to_vec()),
+            ]
+        );
+
+        // Seal the batch and stage another one, then roll it back.
+        let sealed = overlay.seal_epoch();
+        overlay.put(b"a6", b"v6");
+        assert_eq!(overlay.get_value(b"a6").unwrap(), Some(b"v6".to_vec()));
+        overlay.rollback_to(sealed);
+        assert_eq!(overlay.get_value(b"a6").unwrap(), None);
+        // The first batch survives the rollback.
+        assert_eq!(overlay.get_value(b"a3").unwrap(), None);
+        assert_eq!(overlay.get_value(b"a4").unwrap(), Some(b"v4".to_vec()));
+    }
+
+    #[test]
+    fn test_partition_ranges() {
+        // No split points -> whole region as one range.
+        assert_eq!(
+            partition_ranges(b"a", b"z", vec![], 4),
+            vec![(b"a".to_vec(), b"z".to_vec())]
+        );
+        // Split points bound to at most max_parts ranges.
+        let ranges = partition_ranges(
+            b"a",
+            b"z",
+            vec![b"m".to_vec(), b"g".to_vec(), b"t".to_vec()],
+            3,
+        );
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].0, b"a".to_vec());
+        assert_eq!(ranges.last().unwrap().1, b"z".to_vec());
+        // Open upper bound is preserved.
+        let ranges = partition_ranges(b"a", b"", vec![b"m".to_vec()], 4);
+        assert_eq!(ranges.last().unwrap().1, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_scan_parallel() {
+        use futures_cpupool::Builder;
+
+        let path = TempDir::new("test-raftstore").unwrap();
+        let engines = new_temp_engine(&path);
+        let (store, base_data) = load_default_dataset(engines);
+
+        let snap = RegionSnapshot::new(&store);
+        let pool = Builder::new().pool_size(2).create();
+        let states = snap
+            .scan_parallel(
+                &pool,
+                CF_DEFAULT,
+                4,
+                Vec::<(Vec<u8>, Vec<u8>)>::new,
+                |state, k, v| state.push((k.to_vec(), v.to_vec())),
+            )
+            .unwrap();
+
+        let mut data: DataSet = states.into_iter().flatten().collect();
+        data.sort();
+        assert_eq!(data, base_data[1..3].to_vec());
+    }
+
+    #[test]
+    fn test_scan_typed() {
+        let path = TempDir::new("test-raftstore").unwrap();
+        let engines = new_temp_engine(&path);
+        let mut r = Region::new();
+        r.mut_peers().push(Peer::new());
+        r.set_id(10);
+        r.set_start_key(b"k1".to_vec());
+        r.set_end_key(b"k4".to_vec());
+        engines.kv.put_u64(&data_key(b"k1"), 7).expect("");
+        engines.kv.put_u64(&data_key(b"k2"), 9).expect("");
+        let store = new_peer_storage(engines, &r);
+
+        let snap = RegionSnapshot::new(&store);
+        let mut data = vec![];
+        snap.scan_typed(ValueCodec::U64, b"k1", b"k4", false, |key, value| {
+            data.push((key.to_vec(), value));
+            Ok(true)
+        }).unwrap();
+        assert_eq!(
+            data,
+            vec![
+                (b"k1".to_vec(), TypedValue::U64(7)),
+                (b"k2".to_vec(), TypedValue::U64(9)),
+            ]
+        );
+
+        // The raw 8-byte values decode cleanly as bytes too.
+        snap.scan_typed(ValueCodec::Bytes, b"k1", b"k4", false, |_, value| {
+            match value {
+                TypedValue::Bytes(b) => assert_eq!(b.len(), 8),
+                _ => panic!("expected bytes"),
+            }
+            Ok(true)
+        }).unwrap();
+
+        // A value too short for the requested codec terminates the scan with an error.
+        let path = TempDir::new("test-raftstore-typed").unwrap();
+        let engines = new_temp_engine(&path);
+        let (store, _) = load_default_dataset(engines);
+        let snap = RegionSnapshot::new(&store);
+        let res = snap.scan_typed(ValueCodec::U64, b"a2", &[0xFF, 0xFF], false, |_, _| Ok(true));
+        assert!(res.is_err());
+    }
+
     #[test]
     fn test_reverse_iterate_with_lower_bound() {
         let path = TempDir::new("test-raftstore").unwrap();